@@ -0,0 +1,30 @@
+//! Measures the cost of constructing a report with backtrace capture on and
+//! off, run with `cargo bench`
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use stable_eyre::eyre::{eyre, Report};
+use std::env;
+
+#[inline(never)]
+fn construct() -> Report {
+    eyre!("benchmark error")
+}
+
+fn report_construction(c: &mut Criterion) {
+    env::remove_var("RUST_LIB_BACKTRACE");
+    stable_eyre::install().unwrap();
+
+    let mut group = c.benchmark_group("report");
+
+    env::set_var("RUST_BACKTRACE", "0");
+    stable_eyre::reload_config();
+    group.bench_function("capture off", |b| b.iter(|| black_box(construct())));
+
+    env::set_var("RUST_BACKTRACE", "1");
+    stable_eyre::reload_config();
+    group.bench_function("capture on", |b| b.iter(|| black_box(construct())));
+
+    group.finish();
+}
+
+criterion_group!(benches, report_construction);
+criterion_main!(benches);
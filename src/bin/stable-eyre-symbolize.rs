@@ -0,0 +1,34 @@
+//! Resolves the raw backtraces printed by stable-eyre's offline symbolization
+//! mode
+//!
+//! Reads a report from stdin and prints it with every raw backtrace block
+//! resolved against the unstripped executables and shared objects given as
+//! arguments.
+use stable_eyre::eyre::{bail, Result, WrapErr};
+use std::{
+    env,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+fn main() -> Result<()> {
+    stable_eyre::install()?;
+
+    let debug_files: Vec<PathBuf> = env::args_os().skip(1).map(PathBuf::from).collect();
+
+    if debug_files.is_empty() {
+        bail!("usage: stable-eyre-symbolize <DEBUG_FILE>... < report.txt");
+    }
+
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .wrap_err("failed to read the report from stdin")?;
+
+    print!(
+        "{}",
+        stable_eyre::symbolize::symbolize(&input, &debug_files)?
+    );
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use crate::{frame::Omitted, Frame};
+use backtrace::{Backtrace, BacktraceFrame};
+use once_cell::sync::Lazy;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Mutex, MutexGuard},
+};
+
+/// The most frames of the code capturing the backtrace that are recorded
+/// above the frames counted towards `max_frames`
+const CAPTURE_FRAMES: usize = 32;
+
+/// The most frames of the runtime that are recorded below the frames counted
+/// towards `max_frames`
+const RUNTIME_FRAMES: usize = 16;
+
+/// The most frames walked per capture, unless more are recorded, so capturing
+/// a backtrace of a deeply recursive stack stays cheap
+const MAX_DEPTH: usize = 4096;
+
+/// Walks the stack recording only the first `max_frames / 2`, rounded up, and
+/// last `max_frames / 2` frames between the code capturing the backtrace and
+/// the runtime code that runs before `main`
+///
+/// The frames of the capturing code and the runtime are recorded without
+/// counting towards `max_frames`, so the default frame filter can still find
+/// them. The frames in between are counted but never recorded or resolved.
+///
+/// The walk stops after `MAX_DEPTH` frames. The bottom of a deeper stack is
+/// never reached, so only the first frames are kept and the omitted run is
+/// marked as truncated.
+pub(crate) fn capture_bounded(max_frames: usize) -> (Backtrace, Option<Omitted>) {
+    let head_len = max_frames.div_ceil(2);
+    let tail_len = max_frames / 2;
+    let head_cap = CAPTURE_FRAMES + head_len;
+    let tail_cap = tail_len + RUNTIME_FRAMES;
+    let max_depth = MAX_DEPTH.max(head_cap + tail_cap);
+
+    let mut head = Vec::with_capacity(head_cap);
+    let mut tail = VecDeque::with_capacity(tail_cap);
+    let mut walked = 0;
+    let mut truncated = false;
+
+    backtrace::trace(|frame| {
+        if walked == max_depth {
+            truncated = true;
+            return false;
+        }
+
+        if head.len() < head_cap {
+            head.push(BacktraceFrame::from(frame.clone()));
+        } else if tail_cap > 0 {
+            if tail.len() == tail_cap {
+                tail.pop_front();
+            }
+            tail.push_back(BacktraceFrame::from(frame.clone()));
+        }
+
+        walked += 1;
+        true
+    });
+
+    // Frames are positioned in the whole stack, the frames between `head`
+    // and `tail` weren't recorded
+    let tail_start = walked - tail.len();
+    let unrecorded = head.len()..tail_start;
+    let mut frames: Vec<(usize, BacktraceFrame)> = head
+        .into_iter()
+        .enumerate()
+        .chain((tail_start..).zip(tail))
+        .collect();
+
+    let mut kinds = FRAME_KINDS.lock().unwrap_or_else(|e| e.into_inner());
+
+    let skipped = frames
+        .iter()
+        .take(CAPTURE_FRAMES)
+        .take_while(|(_, frame)| frame_kind(&mut kinds, frame).capture)
+        .count();
+    let top = (skipped + head_len).min(walked);
+
+    // Only the frames recorded at the bottom of the stack are resolved to
+    // look for the runtime, unless every frame was recorded. A truncated
+    // stack has no bottom to look at.
+    let bottom = if truncated {
+        walked
+    } else {
+        let runtime = frames
+            .iter()
+            .filter(|(i, _)| *i >= skipped && (unrecorded.is_empty() || *i >= tail_start))
+            .find(|(_, frame)| frame_kind(&mut kinds, frame).runtime_init)
+            .map_or(walked, |(i, _)| *i);
+
+        let bottom = runtime.saturating_sub(tail_len).max(top);
+        if unrecorded.is_empty() {
+            bottom
+        } else {
+            bottom.max(unrecorded.end)
+        }
+    };
+
+    drop(kinds);
+    frames.retain(|(i, _)| *i < top || *i >= bottom);
+
+    let omitted = Some(Omitted {
+        index: top,
+        count: bottom - top,
+        truncated,
+    })
+    .filter(|omitted| omitted.count > 0 || omitted.truncated);
+    let frames: Vec<_> = frames.into_iter().map(|(_, frame)| frame).collect();
+
+    (Backtrace::from(frames), omitted)
+}
+
+/// What a frame's function is, as far as bounding a capture is concerned
+#[derive(Debug, Clone, Copy)]
+struct FrameKind {
+    /// Every symbol of the frame is of the code capturing the backtrace
+    capture: bool,
+    /// Any symbol of the frame is of the runtime code that runs before `main`
+    runtime_init: bool,
+}
+
+/// The kinds of the frames seen so far, by instruction pointer, so each
+/// address is only resolved once, the first time a capture sees it
+static FRAME_KINDS: Lazy<Mutex<HashMap<usize, FrameKind>>> = Lazy::new(Mutex::default);
+
+/// Looks up the kind of a frame, resolving its symbols if the address hasn't
+/// been seen before
+fn frame_kind(
+    kinds: &mut MutexGuard<'_, HashMap<usize, FrameKind>>,
+    frame: &BacktraceFrame,
+) -> FrameKind {
+    let ip = frame.ip() as usize;
+
+    if let Some(kind) = kinds.get(&ip) {
+        return *kind;
+    }
+
+    let mut symbols = Vec::new();
+    backtrace::resolve(frame.ip(), |symbol| {
+        symbols.push(Frame {
+            n: 0,
+            name: symbol.name().map(|name| format!("{:#}", name)),
+            lineno: None,
+            colno: None,
+            filename: None,
+            ip,
+        });
+    });
+
+    let kind = FrameKind {
+        capture: !symbols.is_empty() && symbols.iter().all(Frame::is_capture_code),
+        runtime_init: symbols.iter().any(Frame::is_runtime_init_code),
+    };
+
+    kinds.insert(ip, kind);
+    kind
+}
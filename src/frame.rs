@@ -0,0 +1,655 @@
+use crate::{
+    paths::PathRewrites,
+    theme::{Element, Theme},
+    Verbosity,
+};
+use backtrace::Backtrace;
+use indenter::indented;
+use std::{
+    env, fmt, fs,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+/// Callback for filtering the frames of a backtrace before they are printed
+pub type FilterCallback = dyn Fn(&mut Vec<&Frame>) + Send + Sync + 'static;
+
+/// A single resolved symbol of a captured backtrace
+///
+/// Inlined functions are resolved to multiple symbols for the same
+/// instruction, each of which gets its own `Frame`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Frame {
+    /// The index of this frame in the unfiltered backtrace
+    pub n: usize,
+    /// The demangled name of the function this frame belongs to, if known
+    pub name: Option<String>,
+    /// The line number of this frame's source location, if known
+    pub lineno: Option<u32>,
+    /// The column number of this frame's source location, if known
+    pub colno: Option<u32>,
+    /// The file of this frame's source location, if known
+    pub filename: Option<PathBuf>,
+    /// The instruction pointer of this frame
+    pub ip: usize,
+}
+
+/// A run of frames in the middle of a stack that wasn't captured because of
+/// `HookBuilder::max_frames`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Omitted {
+    /// The position of the first omitted frame
+    pub(crate) index: usize,
+    /// The number of omitted frames
+    pub(crate) count: usize,
+    /// The stack was deeper than the frames walked, so more frames than
+    /// `count` were omitted at the bottom of the stack
+    pub(crate) truncated: bool,
+}
+
+/// A run of frames repeating the frames right before it, as in recursion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Repeat {
+    /// The number of the first repeating frame
+    pub(crate) first: usize,
+    /// The number of the last repeating frame
+    pub(crate) last: usize,
+    /// The number of frames that repeat
+    pub(crate) period: usize,
+    /// The number of times they repeat
+    pub(crate) times: usize,
+}
+
+impl fmt::Display for Repeat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.period {
+            1 => write!(
+                f,
+                "[frames {}-{} repeat the previous frame {} times]",
+                self.first, self.last, self.times
+            ),
+            period => write!(
+                f,
+                "[frames {}-{} repeat the previous {} frames {} times]",
+                self.first, self.last, period, self.times
+            ),
+        }
+    }
+}
+
+/// An entry of a list of frames with its repeating runs folded
+#[derive(Debug)]
+pub(crate) enum Folded<'a> {
+    Frame(&'a Frame),
+    Repeat(Repeat),
+    Omitted(Omitted),
+}
+
+impl Frame {
+    /// Collects the frames of a resolved backtrace, numbering the frames
+    /// after an omitted run as if the omitted frames had been captured
+    ///
+    /// `omitted` is positioned by captured frame, and is returned positioned
+    /// by frame number.
+    pub(crate) fn from_backtrace(
+        backtrace: &Backtrace,
+        omitted: Option<Omitted>,
+    ) -> (Vec<Frame>, Option<Omitted>) {
+        let mut frames = Vec::new();
+        let mut numbered = None;
+        let mut offset = 0;
+
+        for (i, frame) in backtrace.frames().iter().enumerate() {
+            let ip = frame.ip() as usize;
+
+            if let Some(omitted) = omitted.filter(|omitted| omitted.index == i) {
+                numbered = Some(Omitted {
+                    index: frames.len(),
+                    ..omitted
+                });
+                offset = omitted.count;
+            }
+
+            if frame.symbols().is_empty() {
+                frames.push(Frame {
+                    n: frames.len() + offset,
+                    name: None,
+                    lineno: None,
+                    colno: None,
+                    filename: None,
+                    ip,
+                });
+            }
+
+            for symbol in frame.symbols() {
+                frames.push(Frame {
+                    n: frames.len() + offset,
+                    name: symbol.name().map(|name| format!("{:#}", name)),
+                    lineno: symbol.lineno(),
+                    colno: symbol.colno(),
+                    filename: symbol.filename().map(PathBuf::from),
+                    ip,
+                });
+            }
+        }
+
+        // The omitted run is at the bottom of the stack
+        let numbered = numbered.or_else(|| {
+            omitted.map(|omitted| Omitted {
+                index: frames.len(),
+                ..omitted
+            })
+        });
+
+        (frames, numbered)
+    }
+
+    /// Creates unresolved frames from the instruction pointers of a backtrace
+    pub(crate) fn from_ips(ips: &[usize]) -> Vec<Frame> {
+        ips.iter()
+            .enumerate()
+            .map(|(n, &ip)| Frame {
+                n,
+                name: None,
+                lineno: None,
+                colno: None,
+                filename: None,
+                ip,
+            })
+            .collect()
+    }
+
+    /// Is this a frame of the code that captured the backtrace or constructed
+    /// the `eyre::Report`?
+    pub fn is_capture_code(&self) -> bool {
+        const SYM_PREFIXES: &[&str] = &[
+            "backtrace::",
+            "stable_eyre::",
+            "<stable_eyre::",
+            "eyre::",
+            "<eyre::",
+        ];
+        const TRAIT_IMPLS: &[&str] = &[" as eyre::", " as stable_eyre::"];
+        // Frames above `catch_panic` are of the panicking code
+        const BOUNDARIES: &[&str] = &["stable_eyre::panic::catch_panic"];
+
+        let is_trait_impl = match &self.name {
+            Some(name) => {
+                name.starts_with('<') && TRAIT_IMPLS.iter().any(|pattern| name.contains(pattern))
+            }
+            None => false,
+        };
+
+        (is_trait_impl || self.name_starts_with(SYM_PREFIXES)) && !self.name_starts_with(BOUNDARIES)
+    }
+
+    /// Is this a frame of the code that starts a panic and runs the panic
+    /// hook?
+    pub fn is_panic_code(&self) -> bool {
+        const SYM_PREFIXES: &[&str] = &[
+            "std::panicking::begin_panic",
+            "std::panicking::panic_with_hook",
+            "std::panicking::rust_panic_with_hook",
+            "std::panicking::default_hook",
+            "std::sys_common::backtrace::__rust_end_short_backtrace",
+            "std::sys::backtrace::__rust_end_short_backtrace",
+            "rust_begin_unwind",
+            "__rustc::rust_begin_unwind",
+            "core::panicking::",
+            "core::result::unwrap_failed",
+            "core::option::unwrap_failed",
+            "core::option::expect_failed",
+        ];
+
+        self.name_starts_with(SYM_PREFIXES)
+    }
+
+    /// Is this a frame of the runtime code that runs before `main`?
+    pub fn is_runtime_init_code(&self) -> bool {
+        const SYM_PREFIXES: &[&str] = &[
+            "std::rt::lang_start",
+            "std::sys_common::backtrace::__rust_begin_short_backtrace",
+            "std::sys::backtrace::__rust_begin_short_backtrace",
+            "test::run_test",
+        ];
+
+        self.name_starts_with(SYM_PREFIXES)
+    }
+
+    /// Is this a frame of the call shims generated for closures and function
+    /// pointers?
+    pub fn is_call_shim(&self) -> bool {
+        const SYM_PREFIXES: &[&str] = &["core::ops::function::"];
+
+        self.name_starts_with(SYM_PREFIXES)
+    }
+
+    /// Is this a frame of code from a dependency in the cargo registry, a git
+    /// dependency or the rust toolchain?
+    pub fn is_dependency_code(&self) -> bool {
+        const FILE_PATTERNS: &[&str] = &[
+            "/.cargo/registry/src/",
+            "/.cargo/git/checkouts/",
+            "/rustlib/src/rust/",
+            "/rustc/",
+        ];
+
+        match &self.filename {
+            Some(filename) => {
+                let filename = filename.to_string_lossy().replace('\\', "/");
+                FILE_PATTERNS
+                    .iter()
+                    .any(|pattern| filename.contains(pattern))
+            }
+            None => false,
+        }
+    }
+
+    /// Writes the lines of source surrounding this frame's source location,
+    /// marking and painting the line of the frame itself
+    ///
+    /// Nothing is written for dependency code or when the source file can't
+    /// be read.
+    fn fmt_source<W: fmt::Write>(&self, lines: usize, theme: &Theme, f: &mut W) -> fmt::Result {
+        let (filename, lineno) = match (&self.filename, self.lineno) {
+            (Some(filename), Some(lineno)) if !self.is_dependency_code() => {
+                (filename, lineno as usize)
+            }
+            _ => return Ok(()),
+        };
+
+        let file = match fs::File::open(filename) {
+            Ok(file) => file,
+            Err(_) => return Ok(()),
+        };
+
+        let start = lineno.saturating_sub(lines).max(1);
+        let end = lineno + lines;
+        let width = end.to_string().len();
+
+        let source = BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .enumerate()
+            .map(|(i, line)| (i + 1, line))
+            .skip(start - 1)
+            .take(end + 1 - start);
+
+        for (n, line) in source {
+            let row = if line.is_empty() {
+                format!("{:>width$} |", n, width = width)
+            } else {
+                format!("{:>width$} | {}", n, line, width = width)
+            };
+
+            if n == lineno {
+                write!(f, "\n    > {}", theme.paint(Element::CurrentLine, row))?;
+            } else {
+                write!(f, "\n      {}", row)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if both frames are the same call of the same function
+    fn is_same_call(&self, other: &Frame) -> bool {
+        self.ip == other.ip
+            && self.name == other.name
+            && self.filename == other.filename
+            && self.lineno == other.lineno
+            && self.colno == other.colno
+    }
+
+    fn name_starts_with(&self, prefixes: &[&str]) -> bool {
+        match &self.name {
+            Some(name) => prefixes.iter().any(|prefix| name.starts_with(prefix)),
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let formatter = FrameFormatter {
+            theme: &Theme::default(),
+            verbosity: Verbosity::Medium,
+            source_snippets: 0,
+            paths: &PathRewrites::default(),
+            omitted: None,
+        };
+
+        formatter.fmt_frame(self, None, f)
+    }
+}
+
+/// Settings for writing the frames of a backtrace
+pub(crate) struct FrameFormatter<'a> {
+    pub(crate) theme: &'a Theme,
+    pub(crate) verbosity: Verbosity,
+    /// The number of lines of source to print above and below each frame's
+    /// source location, zero disables source snippets
+    pub(crate) source_snippets: usize,
+    /// The rewrites applied to the file path of each frame
+    pub(crate) paths: &'a PathRewrites,
+    /// The frames that weren't captured, marked where they would've been
+    pub(crate) omitted: Option<Omitted>,
+}
+
+impl FrameFormatter<'_> {
+    /// Writes a numbered list of frames, one entry per frame, with repeating
+    /// runs of frames folded into a single line
+    ///
+    /// With `Verbosity::Medium` file paths in the current directory are
+    /// printed relative to it.
+    pub(crate) fn fmt_frames<W: fmt::Write>(&self, frames: &[Frame], f: &mut W) -> fmt::Result {
+        let cwd = match self.verbosity {
+            Verbosity::Medium => env::current_dir().ok(),
+            _ => None,
+        };
+
+        for entry in fold(frames, self.omitted) {
+            let frame = match entry {
+                Folded::Frame(frame) => frame,
+                Folded::Repeat(repeat) => {
+                    write!(f, "\n      {}", repeat)?;
+                    continue;
+                }
+                Folded::Omitted(omitted) if omitted.truncated => {
+                    write!(
+                        f,
+                        "\n      ... at least {} frames omitted ...",
+                        omitted.count
+                    )?;
+                    continue;
+                }
+                Folded::Omitted(omitted) => {
+                    write!(f, "\n      ... {} frames omitted ...", omitted.count)?;
+                    continue;
+                }
+            };
+
+            writeln!(f)?;
+            let mut f = indented(f).ind(frame.n);
+            self.fmt_frame(frame, cwd.as_deref(), &mut f)?;
+
+            if self.source_snippets > 0 {
+                frame.fmt_source(self.source_snippets, self.theme, &mut f)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes a frame's function name and source location, paths are
+    /// rewritten and then shortened to be relative to `cwd` if they're under
+    /// it
+    fn fmt_frame<W: fmt::Write>(
+        &self,
+        frame: &Frame,
+        cwd: Option<&Path>,
+        f: &mut W,
+    ) -> fmt::Result {
+        if self.verbosity == Verbosity::Full {
+            write!(f, "{:#018x} - ", frame.ip)?;
+        }
+
+        let name = frame.name.as_deref().unwrap_or("<unknown>");
+        let element = if frame.is_dependency_code() {
+            Element::Dependency
+        } else {
+            Element::Function
+        };
+
+        write!(f, "{}", self.theme.paint(element, name))?;
+
+        if let Some(filename) = &frame.filename {
+            let filename = self.paths.rewrite(filename);
+            let filename = cwd
+                .and_then(|cwd| filename.strip_prefix(cwd).ok())
+                .unwrap_or(&filename);
+            let location = match frame.lineno {
+                Some(lineno) => format!("{}:{}", filename.display(), lineno),
+                None => filename.display().to_string(),
+            };
+
+            write!(f, "\n    at {}", self.theme.paint(Element::File, location))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The longest run of frames that is folded when it repeats
+const MAX_PERIOD: usize = 32;
+
+/// Folds runs of frames that repeat the frames right before them at least
+/// twice, and marks where the omitted frames would've been
+///
+/// Runs aren't folded across the omitted frames, whatever those were.
+pub(crate) fn fold(frames: &[Frame], omitted: Option<Omitted>) -> Vec<Folded<'_>> {
+    let omitted = match omitted {
+        Some(omitted) => omitted,
+        None => return fold_repeats(frames),
+    };
+
+    // Filters may reorder frames, so the frames aren't necessarily sorted
+    let split = frames
+        .iter()
+        .position(|frame| frame.n >= omitted.index)
+        .unwrap_or(frames.len());
+    let (above, below) = frames.split_at(split);
+
+    let mut folded = fold_repeats(above);
+    folded.push(Folded::Omitted(omitted));
+    folded.extend(fold_repeats(below));
+
+    folded
+}
+
+/// Folds runs of frames that repeat the frames right before them at least
+/// twice, preferring the shortest repeating run
+fn fold_repeats(frames: &[Frame]) -> Vec<Folded<'_>> {
+    let mut folded = Vec::new();
+    let mut i = 0;
+
+    while i < frames.len() {
+        let repeat = (1..=MAX_PERIOD)
+            .take_while(|period| i + period * 3 <= frames.len())
+            .map(|period| (period, repeats(&frames[i..], period)))
+            .find(|&(_, times)| times >= 2);
+
+        match repeat {
+            Some((period, times)) => {
+                let start = i + period;
+                let end = start + period * times;
+
+                folded.extend(frames[i..start].iter().map(Folded::Frame));
+                folded.push(Folded::Repeat(Repeat {
+                    first: frames[start].n,
+                    last: frames[end - 1].n,
+                    period,
+                    times,
+                }));
+                i = end;
+            }
+            None => {
+                folded.push(Folded::Frame(&frames[i]));
+                i += 1;
+            }
+        }
+    }
+
+    folded
+}
+
+/// The number of times the first `period` frames repeat right after
+/// themselves
+fn repeats(frames: &[Frame], period: usize) -> usize {
+    let (first, rest) = frames.split_at(period);
+
+    rest.chunks_exact(period)
+        .take_while(|chunk| {
+            chunk
+                .iter()
+                .zip(first)
+                .all(|(frame, first)| frame.is_same_call(first))
+        })
+        .count()
+}
+
+/// The default frame filter
+///
+/// Removes the frames that captured the backtrace and constructed the report
+/// or started a panic, every frame after `main`, and call shims in between.
+pub(crate) fn default_frame_filter(frames: &mut Vec<&Frame>) {
+    let bottom_cutoff = frames
+        .iter()
+        .position(|frame| frame.is_runtime_init_code())
+        .unwrap_or(frames.len());
+
+    frames.truncate(bottom_cutoff);
+
+    let top_cutoff = frames
+        .iter()
+        .rposition(|frame| frame.is_capture_code() || frame.is_panic_code())
+        .map(|i| i + 1)
+        .unwrap_or(0);
+
+    frames.drain(..top_cutoff);
+    frames.retain(|frame| !frame.is_call_shim());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(n: usize, name: &str) -> Frame {
+        Frame {
+            n,
+            name: Some(name.to_owned()),
+            lineno: Some(1),
+            colno: None,
+            filename: None,
+            ip: name.len(),
+        }
+    }
+
+    fn frames(names: &[&str]) -> Vec<Frame> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(n, name)| frame(n, name))
+            .collect()
+    }
+
+    fn render(frames: &[Frame], omitted: Option<Omitted>) -> String {
+        let formatter = FrameFormatter {
+            theme: &Theme::default(),
+            verbosity: Verbosity::Minimal,
+            source_snippets: 0,
+            paths: &PathRewrites::default(),
+            omitted,
+        };
+        let mut output = String::new();
+        formatter.fmt_frames(frames, &mut output).unwrap();
+        output
+    }
+
+    #[test]
+    #[cfg(feature = "color")]
+    fn paints_current_source_line() {
+        let theme = Theme::new().current_line(owo_colors::Style::new().bold());
+        let lineno = line!();
+        let frame = Frame {
+            filename: Some(PathBuf::from(file!())),
+            lineno: Some(lineno),
+            ..frame(0, "app::main")
+        };
+        let mut output = String::new();
+        frame.fmt_source(1, &theme, &mut output).unwrap();
+        let lines: Vec<_> = output.lines().skip(1).collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("      ") && !lines[0].contains('\u{1b}'));
+        assert!(lines[1].starts_with(&format!("    > \u{1b}[1m{} |", lineno)));
+        assert!(!lines[2].contains('\u{1b}'));
+    }
+
+    #[test]
+    fn folds_recursion() {
+        let frames = frames(&["top", "rec", "rec", "rec", "rec", "rec", "main"]);
+
+        assert_eq!(
+            render(&frames, None),
+            "\n   0: top\n   1: rec\n      [frames 2-5 repeat the previous frame 4 times]\n   6: main"
+        );
+    }
+
+    #[test]
+    fn folds_mutual_recursion() {
+        let frames = frames(&["a", "bb", "a", "bb", "a", "bb", "a", "main"]);
+        let repeats: Vec<_> = fold(&frames, None)
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Folded::Repeat(repeat) => Some(repeat),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            repeats,
+            [Repeat {
+                first: 2,
+                last: 5,
+                period: 2,
+                times: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn keeps_short_runs() {
+        let frames = frames(&["rec", "rec", "main"]);
+
+        assert!(fold(&frames, None)
+            .iter()
+            .all(|entry| matches!(entry, Folded::Frame(_))));
+    }
+
+    #[test]
+    fn does_not_fold_across_omitted_frames() {
+        let mut frames = frames(&["rec", "rec", "rec", "rec", "rec"]);
+        for frame in &mut frames[2..] {
+            frame.n += 100;
+        }
+        let omitted = Some(Omitted {
+            index: 2,
+            count: 100,
+            truncated: false,
+        });
+
+        assert_eq!(
+            render(&frames, omitted),
+            "\n   0: rec\n   1: rec\n      ... 100 frames omitted ...\n 102: rec\n      [frames 103-104 repeat the previous frame 2 times]"
+        );
+    }
+
+    #[test]
+    fn finds_omitted_frames_in_reordered_frames() {
+        let mut frames = frames(&["a", "bb", "ccc"]);
+        frames[2].n = 50;
+        frames.swap(0, 1);
+        let omitted = Some(Omitted {
+            index: 2,
+            count: 48,
+            truncated: false,
+        });
+
+        assert_eq!(
+            render(&frames, omitted),
+            "\n   1: bb\n   0: a\n      ... 48 frames omitted ...\n  50: ccc"
+        );
+    }
+}
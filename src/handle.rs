@@ -0,0 +1,73 @@
+use crate::{Format, Hook, Verbosity};
+use std::{
+    fmt,
+    sync::{atomic::Ordering, Arc},
+};
+
+/// A handle to an installed hook for changing its configuration at runtime
+///
+/// Returned by `HookBuilder::install` and `HookBuilder::install_panic_hook`.
+/// Changes apply to every report, and panic, created afterwards, reports that
+/// already exist keep the configuration they were created with. Handles are
+/// cheap to clone and can be shared between threads.
+///
+/// # Example
+///
+/// ```rust
+/// use stable_eyre::{eyre::eyre, BacktraceExt, HookBuilder, Verbosity};
+///
+/// let handle = HookBuilder::default().install().unwrap();
+///
+/// handle.set_verbosity(Some(Verbosity::Medium));
+/// assert!(eyre!("with a backtrace").backtrace().is_some());
+///
+/// handle.set_verbosity(Some(Verbosity::Minimal));
+/// assert!(eyre!("without a backtrace").backtrace().is_none());
+/// ```
+#[derive(Clone)]
+pub struct HookHandle {
+    pub(crate) hook: Arc<Hook>,
+}
+
+impl HookHandle {
+    /// Sets the format reports are rendered in by their `Debug`
+    /// implementation
+    pub fn set_format(&self, format: Format) {
+        self.hook.format.store(format);
+    }
+
+    /// Sets the verbosity of reports, taking precedence over
+    /// `RUST_LIB_BACKTRACE` and `RUST_BACKTRACE`
+    ///
+    /// `None` goes back to deriving the verbosity from the environment, from
+    /// `STABLE_EYRE_VERBOSITY` if the hook was built with
+    /// `HookBuilder::from_env` and it's set, otherwise from
+    /// `RUST_LIB_BACKTRACE` and `RUST_BACKTRACE`.
+    pub fn set_verbosity(&self, verbosity: Option<Verbosity>) {
+        self.hook.verbosity.store(verbosity);
+    }
+
+    /// Sets whether reports capture a backtrace when neither
+    /// `RUST_LIB_BACKTRACE` nor `RUST_BACKTRACE` is set
+    pub fn set_capture_backtrace_by_default(&self, cond: bool) {
+        self.hook
+            .capture_backtrace_by_default
+            .store(cond, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Debug for HookHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HookHandle")
+            .field("format", &self.hook.format.load())
+            .field("verbosity", &self.hook.verbosity.load())
+            .field(
+                "capture_backtrace_by_default",
+                &self
+                    .hook
+                    .capture_backtrace_by_default
+                    .load(Ordering::Relaxed),
+            )
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,325 @@
+use crate::{
+    frame::{self, Folded},
+    modules::{BuildId, Module},
+    panic::{PanicLocation, PanicMessage},
+    paths::PathRewrites,
+    redact::redact,
+    Frame, Handler,
+};
+use eyre::Report;
+use serde::Serialize;
+use std::{error::Error, iter};
+
+/// Extension trait for rendering an `eyre::Report` as JSON, assuming
+/// stable-eyre's hook is installed.
+pub trait ReportExt {
+    /// Renders the report as a JSON document
+    ///
+    /// The document has the following schema:
+    ///
+    /// ```json
+    /// {
+    ///   "message": "the error message",
+    ///   "type": "std::io::Error",
+    ///   "causes": [{ "message": "...", "type": null }],
+    ///   "location": { "file": "src/main.rs", "line": 6, "column": 21 },
+    ///   "sections": [{ "header": "...", "body": "..." }],
+    ///   "help": [{ "kind": "note", "message": "..." }],
+    ///   "backtrace": [
+    ///     {
+    ///       "index": 7,
+    ///       "function": "usage::main",
+    ///       "file": "/path/to/src/main.rs",
+    ///       "line": 6,
+    ///       "column": 21,
+    ///       "address": "0x000055c1229d7f9e"
+    ///     }
+    ///   ],
+    ///   "backtrace_suppressed": false,
+    ///   "repeated_frames": [{ "first": 12, "last": 411, "period": 3, "times": 133 }],
+    ///   "omitted_frames": { "index": 64, "count": 1843, "truncated": false },
+    ///   "modules": [
+    ///     {
+    ///       "path": "/path/to/usage",
+    ///       "base": "0x000055c1229a0000",
+    ///       "build_id": "745953377fea0c82e5a4a414c472deba74714920"
+    ///     }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// `type` is only known for errors from the standard library and is `null`
+    /// otherwise. `location` is where the report was constructed. `backtrace` is
+    /// `null` when no backtrace was captured, `backtrace_suppressed` is true when
+    /// that is because of `HookBuilder::backtrace_sampling`, and each of a
+    /// frame's fields other than `index` and `address` is `null` when it couldn't
+    /// be resolved. Runs of frames repeating the frames right before them, as in
+    /// recursion, are left out of `backtrace` and listed in `repeated_frames`
+    /// instead. `omitted_frames` is the run of frames that weren't captured
+    /// because of `HookBuilder::max_frames`, numbered like `index`, and is `null`
+    /// when every frame was captured, `truncated` is true when the stack was too
+    /// deep to walk to the bottom and `count` is a lower bound. `modules` lists the main executable and
+    /// every shared object containing a frame of the backtrace, along with the
+    /// address it was loaded at, it is `null` when no backtrace was captured and
+    /// empty on platforms other than Linux. Messages, sections and help entries
+    /// have the redactions added via `HookBuilder::redact` applied.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use stable_eyre::{eyre::eyre, ReportExt, Section};
+    /// stable_eyre::install().unwrap();
+    ///
+    /// let report = eyre!("oh no").note("it broke");
+    /// let json = report.to_json();
+    ///
+    /// assert_eq!(json["message"], "oh no");
+    /// assert_eq!(json["help"][0]["kind"], "note");
+    /// ```
+    fn to_json(&self) -> serde_json::Value;
+}
+
+impl ReportExt for Report {
+    fn to_json(&self) -> serde_json::Value {
+        let handler = self.handler().downcast_ref::<Handler>();
+        let error: &(dyn Error + 'static) = self.as_ref();
+
+        report_json(handler, error)
+    }
+}
+
+/// Renders an error and the contents of its handler as a JSON document
+pub(crate) fn report_json(
+    handler: Option<&Handler>,
+    error: &(dyn Error + 'static),
+) -> serde_json::Value {
+    let redactions = handler.map_or(&[][..], |handler| &handler.redactions[..]);
+    let mut chain = iter::successors(Some(error), |e| (*e).source()).map(|error| JsonError {
+        message: redact(redactions, error.to_string()),
+        type_name: std_type_name(error),
+    });
+    let root = chain
+        .next()
+        .expect("the chain of an error starts with the error itself");
+    let frames = handler.and_then(Handler::frames);
+    let folded = frames
+        .as_ref()
+        .map(|(frames, omitted)| frame::fold(frames, *omitted));
+
+    let report = JsonReport {
+        message: root.message,
+        type_name: root.type_name,
+        causes: chain.collect(),
+        location: handler.and_then(|handler| {
+            handler.location.map(|location| JsonLocation {
+                file: handler.paths.rewrite_file(location.file()).into_owned(),
+                line: location.line(),
+                column: location.column(),
+            })
+        }),
+        sections: handler
+            .into_iter()
+            .flat_map(|handler| &handler.sections)
+            .map(|section| JsonSection {
+                header: redact(redactions, section.header.to_string()),
+                body: redact(redactions, section.body.to_string()),
+            })
+            .collect(),
+        help: handler
+            .into_iter()
+            .flat_map(|handler| &handler.help)
+            .map(|help| JsonHelp {
+                kind: help.label().to_lowercase(),
+                message: redact(redactions, help.body().to_string()),
+            })
+            .collect(),
+        backtrace: handler.zip(folded.as_ref()).map(|(handler, folded)| {
+            folded
+                .iter()
+                .filter_map(|entry| match entry {
+                    Folded::Frame(frame) => Some(JsonFrame::new(frame, &handler.paths)),
+                    _ => None,
+                })
+                .collect()
+        }),
+        backtrace_suppressed: handler.is_some_and(|handler| handler.backtrace_suppressed),
+        repeated_frames: folded.as_ref().map(|folded| {
+            folded
+                .iter()
+                .filter_map(|entry| match entry {
+                    Folded::Repeat(repeat) => Some(JsonRepeat {
+                        first: repeat.first,
+                        last: repeat.last,
+                        period: repeat.period,
+                        times: repeat.times,
+                    }),
+                    _ => None,
+                })
+                .collect()
+        }),
+        omitted_frames: frames
+            .as_ref()
+            .and_then(|(_, omitted)| *omitted)
+            .map(|omitted| JsonOmitted {
+                index: omitted.index,
+                count: omitted.count,
+                truncated: omitted.truncated,
+            }),
+        modules: handler
+            .and_then(Handler::modules)
+            .map(|modules| modules.iter().map(JsonModule::from).collect()),
+    };
+
+    serde_json::to_value(report).expect("reports only contain serializable data")
+}
+
+/// Renders a panic as a JSON document with the same schema as a report,
+/// `location` is where the panic occurred
+pub(crate) fn panic_json(
+    handler: &Handler,
+    message: &PanicMessage,
+    location: Option<&PanicLocation>,
+) -> serde_json::Value {
+    let mut json = report_json(Some(handler), message);
+    json["location"] = serde_json::to_value(location.map(|location| JsonLocation {
+        file: location.file.clone(),
+        line: location.line,
+        column: location.column,
+    }))
+    .expect("locations only contain serializable data");
+
+    json
+}
+
+#[derive(Serialize)]
+struct JsonReport {
+    message: String,
+    #[serde(rename = "type")]
+    type_name: Option<&'static str>,
+    causes: Vec<JsonError>,
+    location: Option<JsonLocation>,
+    sections: Vec<JsonSection>,
+    help: Vec<JsonHelp>,
+    backtrace: Option<Vec<JsonFrame>>,
+    backtrace_suppressed: bool,
+    repeated_frames: Option<Vec<JsonRepeat>>,
+    omitted_frames: Option<JsonOmitted>,
+    modules: Option<Vec<JsonModule>>,
+}
+
+#[derive(Serialize)]
+struct JsonError {
+    message: String,
+    #[serde(rename = "type")]
+    type_name: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct JsonLocation {
+    file: String,
+    line: u32,
+    column: u32,
+}
+
+#[derive(Serialize)]
+struct JsonSection {
+    header: String,
+    body: String,
+}
+
+#[derive(Serialize)]
+struct JsonHelp {
+    kind: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct JsonFrame {
+    index: usize,
+    function: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    column: Option<u32>,
+    address: String,
+}
+
+impl JsonFrame {
+    fn new(frame: &Frame, paths: &PathRewrites) -> Self {
+        Self {
+            index: frame.n,
+            function: frame.name.clone(),
+            file: frame
+                .filename
+                .as_ref()
+                .map(|filename| paths.rewrite(filename).display().to_string()),
+            line: frame.lineno,
+            column: frame.colno,
+            address: format!("{:#018x}", frame.ip),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonRepeat {
+    first: usize,
+    last: usize,
+    period: usize,
+    times: usize,
+}
+
+#[derive(Serialize)]
+struct JsonOmitted {
+    index: usize,
+    count: usize,
+    truncated: bool,
+}
+
+#[derive(Serialize)]
+struct JsonModule {
+    path: String,
+    base: String,
+    build_id: Option<String>,
+}
+
+impl From<&Module> for JsonModule {
+    fn from(module: &Module) -> Self {
+        Self {
+            path: module.path.display().to_string(),
+            base: format!("{:#018x}", module.base),
+            build_id: module
+                .build_id
+                .as_ref()
+                .map(|build_id| BuildId(build_id).to_string()),
+        }
+    }
+}
+
+/// Identifies the errors of the standard library by downcasting
+fn std_type_name(error: &(dyn Error + 'static)) -> Option<&'static str> {
+    macro_rules! known_types {
+        ($($ty:ty),* $(,)?) => {
+            $(
+                if error.is::<$ty>() {
+                    return Some(stringify!($ty));
+                }
+            )*
+        };
+    }
+
+    known_types!(
+        std::io::Error,
+        std::fmt::Error,
+        std::env::VarError,
+        std::num::ParseIntError,
+        std::num::ParseFloatError,
+        std::num::TryFromIntError,
+        std::str::ParseBoolError,
+        std::str::Utf8Error,
+        std::string::FromUtf8Error,
+        std::char::ParseCharError,
+        std::net::AddrParseError,
+        std::time::SystemTimeError,
+    );
+
+    None
+}
@@ -0,0 +1,140 @@
+use crate::{section::private, Handler, LazyBacktrace, Verbosity};
+use eyre::Report;
+use std::{fmt, panic::Location};
+
+/// What is recorded for each context layer added with `LayerExt`
+///
+/// Configured via `HookBuilder::layer_capture`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LayerCapture {
+    /// Nothing is recorded, `LayerExt` behaves exactly like `WrapErr`
+    Off,
+    /// The location of each layer is recorded and printed under its message
+    Location,
+    /// The location and an unresolved backtrace of each layer are recorded,
+    /// backtraces are only captured when the report's `Verbosity` isn't
+    /// `Verbosity::Minimal`
+    LocationAndBacktrace,
+}
+
+/// The location and backtrace recorded for a single context layer
+#[derive(Debug)]
+pub(crate) struct Layer {
+    /// The length of the error chain right after this layer was added
+    pub(crate) depth: usize,
+    pub(crate) location: &'static Location<'static>,
+    pub(crate) backtrace: Option<LazyBacktrace>,
+}
+
+/// Extension trait for wrapping errors in context layers that record where
+/// they were added, assuming stable-eyre's hook is installed.
+///
+/// What each layer records is configured via `HookBuilder::layer_capture`,
+/// by default nothing is recorded and these methods behave exactly like the
+/// methods of `WrapErr`. Recorded locations are printed under their message in
+/// the `Caused by:` section, showing the path an error propagated through.
+///
+/// # Example
+///
+/// ```rust
+/// use stable_eyre::{eyre::eyre, HookBuilder, LayerCapture, LayerExt};
+///
+/// HookBuilder::default()
+///     .layer_capture(LayerCapture::Location)
+///     .install()
+///     .unwrap();
+///
+/// let report = eyre!("connection reset").wrap_err_layer("failed to fetch config");
+/// let line = line!() - 1;
+///
+/// assert!(format!("{:?}", report).contains(&format!("at {}:{}", file!(), line)));
+/// ```
+pub trait LayerExt: private::Sealed {
+    /// The return type of each method after adding a layer
+    type Return;
+
+    /// Wrap the error value with a new adhoc error, recording the layer's
+    /// location
+    fn wrap_err_layer<D>(self, msg: D) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static;
+
+    /// Wrap the error value with a new adhoc error that is evaluated lazily
+    /// only once an error does occur, recording the layer's location
+    fn wrap_err_layer_with<D, F>(self, msg: F) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> D;
+}
+
+fn push_layer(mut report: Report, location: &'static Location<'static>) -> Report {
+    let depth = report.chain().count();
+
+    if let Some(handler) = report.handler_mut().downcast_mut::<Handler>() {
+        let backtrace = match handler.layer_capture {
+            LayerCapture::Off => return report,
+            LayerCapture::Location => None,
+            LayerCapture::LocationAndBacktrace if handler.verbosity == Verbosity::Minimal => None,
+            LayerCapture::LocationAndBacktrace => Some(LazyBacktrace::capture(handler.max_frames)),
+        };
+
+        handler.layers.push(Layer {
+            depth,
+            location,
+            backtrace,
+        });
+    }
+
+    report
+}
+
+impl LayerExt for Report {
+    type Return = Report;
+
+    #[track_caller]
+    fn wrap_err_layer<D>(self, msg: D) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+    {
+        push_layer(self.wrap_err(msg), Location::caller())
+    }
+
+    #[track_caller]
+    fn wrap_err_layer_with<D, F>(self, msg: F) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> D,
+    {
+        push_layer(self.wrap_err(msg()), Location::caller())
+    }
+}
+
+impl<T, E> LayerExt for Result<T, E>
+where
+    E: Into<Report>,
+{
+    type Return = Result<T, Report>;
+
+    #[track_caller]
+    fn wrap_err_layer<D>(self, msg: D) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error) => Err(error.into().wrap_err_layer(msg)),
+        }
+    }
+
+    #[track_caller]
+    fn wrap_err_layer_with<D, F>(self, msg: F) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> D,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error) => Err(error.into().wrap_err_layer_with(msg)),
+        }
+    }
+}
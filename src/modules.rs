@@ -0,0 +1,279 @@
+//! The executable and shared objects loaded into the current process
+
+use std::{env, fmt, path::PathBuf};
+
+/// An executable or shared object loaded into the current process
+#[derive(Debug, Clone)]
+pub(crate) struct Module {
+    /// The path the module was loaded from
+    pub(crate) path: PathBuf,
+    /// The address the module was loaded at, which is added to the virtual
+    /// addresses in the module's file to get their address in memory
+    pub(crate) base: usize,
+    /// The GNU build-id of the module, if it has one
+    pub(crate) build_id: Option<Vec<u8>>,
+    /// The address ranges the module's segments were loaded into
+    pub(crate) segments: Vec<(usize, usize)>,
+}
+
+impl Module {
+    /// Does any of this module's segments contain `ip`?
+    pub(crate) fn contains(&self, ip: usize) -> bool {
+        self.segments
+            .iter()
+            .any(|&(start, end)| start <= ip && ip < end)
+    }
+}
+
+/// Formats a build-id as a lowercase hexadecimal string
+pub(crate) struct BuildId<'a>(pub(crate) &'a [u8]);
+
+impl fmt::Display for BuildId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists the main executable and every other loaded module containing at
+/// least one of `ips`, in load order
+pub(crate) fn backtrace_modules(ips: &[usize]) -> Vec<Module> {
+    loaded_modules()
+        .into_iter()
+        .enumerate()
+        .filter(|(i, module)| *i == 0 || ips.iter().any(|&ip| module.contains(ip)))
+        .map(|(_, module)| module)
+        .collect()
+}
+
+/// Writes one line per module with its load address, build-id and path
+pub(crate) fn fmt_modules<W: fmt::Write>(modules: &[Module], f: &mut W) -> fmt::Result {
+    for module in modules {
+        write!(f, "\n{:#018x} ", module.base)?;
+
+        match &module.build_id {
+            Some(build_id) => write!(f, "{}", BuildId(build_id))?,
+            None => write!(f, "<no build-id>")?,
+        }
+
+        write!(f, " {}", module.path.display())?;
+    }
+
+    Ok(())
+}
+
+/// The first line of a raw backtrace block
+pub(crate) const BLOCK_BEGIN: &str = "begin stable-eyre-backtrace v1";
+/// The last line of a raw backtrace block
+pub(crate) const BLOCK_END: &str = "end stable-eyre-backtrace";
+
+/// Writes the instruction pointers of a backtrace along with the modules they
+/// belong to, in the format read by `stable-eyre-symbolize`
+///
+/// ```text
+/// begin stable-eyre-backtrace v1
+/// module 0 base=0x000055c1229a0000 build-id=8f3c...e1 path=/usr/bin/app
+/// frame 0 ip=0x000055c1229d7f9e module=0
+/// frame 1 ip=0x00007f0e4c22a1ca module=-
+/// end stable-eyre-backtrace
+/// ```
+///
+/// Only the modules containing at least one of the frames are listed.
+pub(crate) fn fmt_raw_backtrace<W: fmt::Write>(
+    ips: &[usize],
+    modules: &[Module],
+    f: &mut W,
+) -> fmt::Result {
+    let owners: Vec<Option<usize>> = ips
+        .iter()
+        .map(|&ip| modules.iter().position(|module| module.contains(ip)))
+        .collect();
+    let mut used: Vec<usize> = owners.iter().flatten().copied().collect();
+    used.sort_unstable();
+    used.dedup();
+
+    write!(f, "{}", BLOCK_BEGIN)?;
+
+    for (i, &index) in used.iter().enumerate() {
+        let module = &modules[index];
+        write!(f, "\nmodule {} base={:#018x} build-id=", i, module.base)?;
+
+        match &module.build_id {
+            Some(build_id) => write!(f, "{}", BuildId(build_id))?,
+            None => write!(f, "-")?,
+        }
+
+        write!(f, " path={}", module.path.display())?;
+    }
+
+    for (n, (ip, owner)) in ips.iter().zip(&owners).enumerate() {
+        write!(f, "\nframe {} ip={:#018x} module=", n, ip)?;
+
+        match owner.and_then(|owner| used.iter().position(|&index| index == owner)) {
+            Some(i) => write!(f, "{}", i)?,
+            None => write!(f, "-")?,
+        }
+    }
+
+    write!(f, "\n{}", BLOCK_END)
+}
+
+/// Lists the modules currently loaded into the process, starting with the main
+/// executable
+///
+/// Only supported on Linux, every other platform returns no modules.
+#[cfg(target_os = "linux")]
+pub(crate) fn loaded_modules() -> Vec<Module> {
+    use libc::{c_int, c_void, dl_iterate_phdr, dl_phdr_info, PT_LOAD, PT_NOTE};
+    use std::{ffi::CStr, slice};
+
+    unsafe extern "C" fn callback(
+        info: *mut dl_phdr_info,
+        _size: usize,
+        data: *mut c_void,
+    ) -> c_int {
+        let modules = &mut *(data as *mut Vec<Module>);
+        let info = &*info;
+        let base = info.dlpi_addr as usize;
+
+        let name = if info.dlpi_name.is_null() {
+            &[][..]
+        } else {
+            CStr::from_ptr(info.dlpi_name).to_bytes()
+        };
+
+        // The main executable is reported without a name
+        let path = if name.is_empty() {
+            env::current_exe().unwrap_or_default()
+        } else {
+            PathBuf::from(String::from_utf8_lossy(name).into_owned())
+        };
+
+        let headers = if info.dlpi_phdr.is_null() {
+            &[][..]
+        } else {
+            slice::from_raw_parts(info.dlpi_phdr, info.dlpi_phnum as usize)
+        };
+
+        let mut segments = Vec::new();
+        let mut build_id = None;
+
+        for header in headers {
+            let start = base.wrapping_add(header.p_vaddr as usize);
+            let len = header.p_memsz as usize;
+
+            match header.p_type {
+                PT_LOAD => segments.push((start, start.wrapping_add(len))),
+                PT_NOTE if build_id.is_none() => {
+                    let notes = slice::from_raw_parts(start as *const u8, len);
+                    build_id = find_build_id(notes);
+                }
+                _ => {}
+            }
+        }
+
+        // Skip the vdso and other modules that weren't loaded from a file
+        if !segments.is_empty() && path.as_os_str() != "linux-vdso.so.1" {
+            modules.push(Module {
+                path,
+                base,
+                build_id,
+                segments,
+            });
+        }
+
+        0
+    }
+
+    let mut modules = Vec::new();
+
+    unsafe {
+        dl_iterate_phdr(
+            Some(callback),
+            &mut modules as *mut Vec<Module> as *mut c_void,
+        );
+    }
+
+    modules
+}
+
+/// Lists the modules currently loaded into the process
+///
+/// Only supported on Linux, every other platform returns no modules.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn loaded_modules() -> Vec<Module> {
+    Vec::new()
+}
+
+/// Finds the GNU build-id in the contents of an ELF note segment
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn find_build_id(mut notes: &[u8]) -> Option<Vec<u8>> {
+    const NT_GNU_BUILD_ID: u32 = 3;
+
+    fn read_u32(bytes: &[u8]) -> Option<u32> {
+        let bytes = bytes.get(..4)?;
+        Some(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn align(len: usize) -> usize {
+        (len + 3) & !3
+    }
+
+    while notes.len() >= 12 {
+        let name_len = read_u32(notes)? as usize;
+        let desc_len = read_u32(&notes[4..])? as usize;
+        let kind = read_u32(&notes[8..])?;
+
+        let name_start = 12;
+        let desc_start = name_start + align(name_len);
+        let next = desc_start + align(desc_len);
+
+        if kind == NT_GNU_BUILD_ID && notes.get(name_start..name_start + name_len)? == b"GNU\0" {
+            return notes
+                .get(desc_start..desc_start + desc_len)
+                .map(<[u8]>::to_vec);
+        }
+
+        notes = notes.get(next..)?;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(kind: u32, name: &[u8], desc: &[u8]) -> Vec<u8> {
+        let mut note = Vec::new();
+        note.extend_from_slice(&(name.len() as u32).to_ne_bytes());
+        note.extend_from_slice(&(desc.len() as u32).to_ne_bytes());
+        note.extend_from_slice(&kind.to_ne_bytes());
+        note.extend_from_slice(name);
+        note.resize((note.len() + 3) & !3, 0);
+        note.extend_from_slice(desc);
+        note.resize((note.len() + 3) & !3, 0);
+        note
+    }
+
+    #[test]
+    fn find_build_id_skips_other_notes() {
+        let mut notes = note(1, b"GNU\0", &[9; 16]);
+        notes.extend(note(3, b"Go\0", &[8; 5]));
+        notes.extend(note(3, b"GNU\0", &[0xde, 0xad, 0xbe, 0xef, 0x01]));
+
+        assert_eq!(
+            find_build_id(&notes),
+            Some(vec![0xde, 0xad, 0xbe, 0xef, 0x01])
+        );
+    }
+
+    #[test]
+    fn find_build_id_without_build_id() {
+        assert_eq!(find_build_id(&note(1, b"GNU\0", &[9; 16])), None);
+        assert_eq!(find_build_id(&[0; 7]), None);
+    }
+}
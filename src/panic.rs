@@ -0,0 +1,243 @@
+use crate::{section::CustomSection, theme::Element, Handler, Hook, LazyBacktrace, Section};
+use eyre::Report;
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    error::Error,
+    fmt,
+    io::{self, Write},
+    panic::{self, UnwindSafe},
+    sync::Arc,
+    thread,
+};
+
+thread_local! {
+    /// The number of `catch_panic` calls the current thread is inside of
+    static CATCHING: Cell<usize> = const { Cell::new(0) };
+    /// The panics stashed by the panic hook while inside of `catch_panic` on
+    /// the current thread, that weren't turned into a report yet
+    static CAUGHT: RefCell<Vec<PanicReport>> = const { RefCell::new(Vec::new()) };
+    /// Set while `catch_panic` constructs the report of a caught panic, which
+    /// takes the traces captured by the panic hook
+    static REBUILDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Sets the panic hook to print panics with the same layout as reports
+///
+/// Panics inside of `catch_panic` aren't printed right away, they're stashed
+/// for `catch_panic` to turn into a report instead.
+pub(crate) fn install(hook: Arc<Hook>) {
+    panic::set_hook(Box::new(move |info| {
+        let location = info.location().map(|location| PanicLocation {
+            file: hook.paths.rewrite_file(location.file()).into_owned(),
+            line: location.line(),
+            column: location.column(),
+        });
+        let report = PanicReport::new(&hook, info.payload(), location);
+
+        if CATCHING.with(Cell::get) > 0 {
+            CAUGHT.with(|caught| caught.borrow_mut().push(report));
+        } else {
+            print(&report);
+        }
+    }));
+}
+
+/// Invokes a closure, converting a panic into an `eyre::Report`
+///
+/// When the panic hook was installed with `HookBuilder::install_panic_hook`
+/// the panic isn't printed, and the report carries the backtrace and span
+/// trace captured where the panic occurred rather than where it was caught,
+/// along with a `Panic location:` section. Otherwise the panic is printed by
+/// the current panic hook and the report only carries the panic message.
+///
+/// Panics inside of `f` that are caught before they reach `catch_panic`, by
+/// `std::panic::catch_unwind` for example, are printed by the panic hook once
+/// `catch_panic` returns.
+///
+/// # Example
+///
+/// ```rust
+/// use stable_eyre::{catch_panic, HookBuilder};
+///
+/// HookBuilder::default().install_panic_hook().unwrap();
+///
+/// let report = catch_panic(|| -> u32 { panic!("plugin crashed") }).unwrap_err();
+/// assert!(report.to_string().ends_with("panicked: plugin crashed"));
+/// assert!(format!("{:?}", report).contains("Panic location:"));
+/// ```
+#[track_caller]
+pub fn catch_panic<F, T>(f: F) -> Result<T, Report>
+where
+    F: FnOnce() -> T + UnwindSafe,
+{
+    CATCHING.with(|catching| catching.set(catching.get() + 1));
+    let result = panic::catch_unwind(f);
+    CATCHING.with(|catching| catching.set(catching.get() - 1));
+
+    let mut stashed = CAUGHT.with(|caught| caught.take());
+    let payload = match result {
+        Ok(t) => {
+            print_stashed(stashed);
+            return Ok(t);
+        }
+        Err(payload) => payload,
+    };
+
+    // The last panic is the one that unwound out of `f`
+    let caught = stashed.pop();
+    print_stashed(stashed);
+
+    let caught = match caught {
+        Some(caught) => caught,
+        None => return Err(Report::new(PanicMessage::new(&*payload))),
+    };
+
+    REBUILDING.with(|rebuilding| rebuilding.set(true));
+    let mut report = Report::new(caught.message);
+    REBUILDING.with(|rebuilding| rebuilding.set(false));
+
+    if let Some(handler) = report.handler_mut().downcast_mut::<Handler>() {
+        handler.backtrace = caught.handler.backtrace;
+        #[cfg(feature = "tracing")]
+        {
+            handler.span_trace = caught.handler.span_trace;
+        }
+    }
+
+    Err(match caught.location {
+        Some(location) => report.section("Panic location", location),
+        None => report,
+    })
+}
+
+/// Returns true while `catch_panic` constructs the report of a caught panic,
+/// whose traces shouldn't be captured again
+pub(crate) fn is_rebuilding() -> bool {
+    REBUILDING.with(Cell::get)
+}
+
+/// Prints the panics that were caught inside of `catch_panic` before they
+/// reached it
+fn print_stashed(stashed: Vec<PanicReport>) {
+    for report in stashed {
+        print(&report);
+    }
+}
+
+/// Writes a panic to stderr, ignoring errors like the default panic hook
+/// does, since panicking inside of the panic hook aborts the process
+fn print(report: &PanicReport) {
+    let _ = writeln!(io::stderr().lock(), "{}", report);
+}
+
+/// The message of a panic, prefixed with the name of the panicking thread
+#[derive(Debug)]
+pub(crate) struct PanicMessage(String);
+
+impl PanicMessage {
+    fn new(payload: &(dyn Any + Send)) -> Self {
+        PanicMessage(format!(
+            "thread '{}' panicked: {}",
+            thread::current().name().unwrap_or("<unnamed>"),
+            payload_str(payload)
+        ))
+    }
+}
+
+impl fmt::Display for PanicMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for PanicMessage {}
+
+/// The location a panic occurred at
+#[derive(Debug)]
+pub(crate) struct PanicLocation {
+    pub(crate) file: String,
+    pub(crate) line: u32,
+    pub(crate) column: u32,
+}
+
+impl fmt::Display for PanicLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// A panic along with the backtrace and sections captured by the panic hook
+pub(crate) struct PanicReport {
+    handler: Handler,
+    message: PanicMessage,
+    location: Option<PanicLocation>,
+}
+
+impl PanicReport {
+    fn new(hook: &Hook, payload: &(dyn Any + Send), location: Option<PanicLocation>) -> Self {
+        let message = PanicMessage::new(payload);
+
+        let mut handler = hook.make_handler(&message);
+        if handler.sampler.take().is_some() {
+            handler.backtrace = Some(LazyBacktrace::capture(handler.max_frames));
+        }
+        handler.sections = hook
+            .panic_sections
+            .iter()
+            .map(|section| CustomSection {
+                header: Box::new(section.header.to_string()),
+                body: Box::new(section.body.to_string()),
+                alternate_only: section.alternate_only,
+            })
+            .collect();
+
+        Self {
+            handler,
+            message,
+            location,
+        }
+    }
+}
+
+impl fmt::Display for PanicReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let handler = &self.handler;
+
+        #[cfg(feature = "serde")]
+        if handler.format == crate::Format::Json {
+            let json = crate::json::panic_json(handler, &self.message, self.location.as_ref());
+
+            return write!(f, "{}", json);
+        }
+
+        write!(
+            f,
+            "{}",
+            handler
+                .theme
+                .paint(Element::Message, handler.redacted(&self.message))
+        )?;
+
+        if let Some(location) = &self.location {
+            write!(
+                f,
+                "\n\nLocation: {}",
+                handler.theme.paint(Element::File, location)
+            )?;
+        }
+
+        handler.fmt_trailer(f)
+    }
+}
+
+/// The message of a panic payload created by `panic!`
+fn payload_str(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
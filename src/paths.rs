@@ -0,0 +1,153 @@
+use std::{
+    borrow::Cow,
+    panic::Location,
+    path::{Path, PathBuf},
+};
+
+/// The rewrites applied to the file paths of frames and locations before
+/// they are printed
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PathRewrites {
+    /// Prefixes removed from the start of paths, the first matching prefix
+    /// is removed
+    pub(crate) strip_prefixes: Vec<PathBuf>,
+    /// Collapse the cargo registry and the rust sysroot to `<crate>` and
+    /// `<rust>`
+    pub(crate) collapse_dependencies: bool,
+}
+
+impl PathRewrites {
+    /// Rewrites a path, returning it unchanged when no rewrite applies
+    pub(crate) fn rewrite<'a>(&self, path: &'a Path) -> Cow<'a, Path> {
+        if let Some(stripped) = self
+            .strip_prefixes
+            .iter()
+            .find_map(|prefix| path.strip_prefix(prefix).ok())
+        {
+            return Cow::Borrowed(stripped);
+        }
+
+        if self.collapse_dependencies {
+            if let Some(collapsed) = collapse_dependency(path) {
+                return Cow::Owned(collapsed);
+            }
+        }
+
+        Cow::Borrowed(path)
+    }
+
+    /// Rewrites the path of a source file recorded by the compiler
+    pub(crate) fn rewrite_file<'a>(&self, file: &'a str) -> Cow<'a, str> {
+        match self.rewrite(Path::new(file)) {
+            Cow::Borrowed(path) => path.to_string_lossy(),
+            Cow::Owned(path) => Cow::Owned(path.to_string_lossy().into_owned()),
+        }
+    }
+
+    /// Renders a location as `file:line:column` with its path rewritten
+    pub(crate) fn rewrite_location(&self, location: &Location<'_>) -> String {
+        format!(
+            "{}:{}:{}",
+            self.rewrite_file(location.file()),
+            location.line(),
+            location.column()
+        )
+    }
+}
+
+/// Collapses the directories of the cargo registry, cargo's git checkouts
+/// and the rust sysroot at the start of a path
+fn collapse_dependency(path: &Path) -> Option<PathBuf> {
+    /// The placeholder, the pattern marking the directory to collapse, and
+    /// the number of path components after the pattern that are collapsed
+    const COLLAPSES: &[(&str, &str, usize)] = &[
+        ("<crate>", "/.cargo/registry/src/", 1),
+        ("<crate>", "/cargo/registry/src/", 1),
+        ("<crate>", "/.cargo/git/checkouts/", 2),
+        ("<crate>", "/cargo/git/checkouts/", 2),
+        ("<rust>", "/rustlib/src/rust/", 0),
+        ("<rust>", "/rustc/", 1),
+    ];
+
+    let path = path.to_string_lossy().replace('\\', "/");
+
+    COLLAPSES
+        .iter()
+        .find_map(|&(placeholder, pattern, components)| {
+            let (_, mut rest) = path.split_once(pattern)?;
+
+            for _ in 0..components {
+                rest = rest.split_once('/')?.1;
+            }
+
+            Some(PathBuf::from(format!("{}/{}", placeholder, rest)))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collapse(path: &str) -> Option<String> {
+        collapse_dependency(Path::new(path)).map(|path| path.display().to_string())
+    }
+
+    #[test]
+    fn collapses_registry_paths() {
+        assert_eq!(
+            collapse(
+                "/home/user/.cargo/registry/src/index.crates.io-6f17d22bba15001f/tokio-1.x/src/lib.rs"
+            )
+            .as_deref(),
+            Some("<crate>/tokio-1.x/src/lib.rs"),
+        );
+        assert_eq!(
+            collapse("/home/user/.cargo/git/checkouts/tokio-1a2b3c/4d5e6f7/tokio/src/lib.rs")
+                .as_deref(),
+            Some("<crate>/tokio/src/lib.rs"),
+        );
+    }
+
+    #[test]
+    fn collapses_toolchain_paths() {
+        assert_eq!(
+            collapse(
+                "/rustc/90b35a6239c3d8bdabc530a6a0816f7ff89a0aaf/library/core/src/ops/function.rs"
+            )
+            .as_deref(),
+            Some("<rust>/library/core/src/ops/function.rs"),
+        );
+        assert_eq!(
+            collapse(
+                "/home/user/.rustup/toolchains/stable/lib/rustlib/src/rust/library/std/src/rt.rs"
+            )
+            .as_deref(),
+            Some("<rust>/library/std/src/rt.rs"),
+        );
+    }
+
+    #[test]
+    fn keeps_other_paths() {
+        assert_eq!(collapse("/home/user/app/src/main.rs"), None);
+        assert_eq!(collapse("src/main.rs"), None);
+    }
+
+    #[test]
+    fn prefixes_take_precedence() {
+        let paths = PathRewrites {
+            strip_prefixes: vec![PathBuf::from("/home/user/.cargo")],
+            collapse_dependencies: true,
+        };
+
+        assert_eq!(
+            paths.rewrite_file("/home/user/.cargo/registry/src/index/tokio-1.x/src/lib.rs"),
+            "registry/src/index/tokio-1.x/src/lib.rs",
+        );
+        assert_eq!(
+            paths.rewrite_file(
+                "/rustc/90b35a6239c3d8bdabc530a6a0816f7ff89a0aaf/library/std/src/rt.rs"
+            ),
+            "<rust>/library/std/src/rt.rs",
+        );
+    }
+}
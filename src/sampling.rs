@@ -0,0 +1,83 @@
+use std::{
+    collections::HashMap,
+    panic::Location,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Decides which reports capture a backtrace, per call site
+///
+/// A call site is the location a report was constructed at.
+#[derive(Debug)]
+pub(crate) struct Sampler {
+    /// Only every nth report at a call site captures a backtrace
+    every: Option<u32>,
+    /// At most this many reports per second at a call site capture a
+    /// backtrace
+    per_second: Option<u32>,
+    sites: Mutex<HashMap<&'static Location<'static>, Site>>,
+}
+
+#[derive(Debug)]
+struct Site {
+    /// The number of reports at this call site to skip before the next one
+    /// that's sampled
+    until_sample: u32,
+    /// The start of the current one second window
+    window: Instant,
+    /// The number of backtraces captured during the current window, only
+    /// counted when there's a rate limit
+    captured: u32,
+}
+
+impl Sampler {
+    pub(crate) fn new(every: Option<u32>, per_second: Option<u32>) -> Option<Self> {
+        if every.is_none() && per_second.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            every,
+            per_second,
+            sites: Mutex::default(),
+        })
+    }
+
+    /// Returns true if a report constructed at `location` should capture a
+    /// backtrace, counting the report either way
+    ///
+    /// The first report at a call site is always sampled.
+    pub(crate) fn sample(&self, location: &'static Location<'static>) -> bool {
+        let now = Instant::now();
+        let mut sites = self.sites.lock().unwrap_or_else(|e| e.into_inner());
+        let site = sites.entry(location).or_insert_with(|| Site {
+            until_sample: 0,
+            window: now,
+            captured: 0,
+        });
+
+        if let Some(every) = self.every {
+            if site.until_sample > 0 {
+                site.until_sample -= 1;
+                return false;
+            }
+
+            site.until_sample = every.saturating_sub(1);
+        }
+
+        if let Some(per_second) = self.per_second {
+            if now.duration_since(site.window) >= Duration::from_secs(1) {
+                site.window = now;
+                site.captured = 0;
+            }
+
+            if site.captured >= per_second {
+                return false;
+            }
+
+            site.captured += 1;
+        }
+
+        true
+    }
+}
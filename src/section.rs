@@ -0,0 +1,421 @@
+use crate::{
+    redact::{Redacted, Redaction},
+    theme::{Element, Theme},
+    Handler,
+};
+use eyre::Report;
+use indenter::indented;
+use std::fmt;
+
+/// A piece of guidance attached to a report, printed after the `Caused by:`
+/// section
+pub(crate) enum HelpInfo {
+    Note(Box<dyn fmt::Display + Send + Sync + 'static>),
+    Warning(Box<dyn fmt::Display + Send + Sync + 'static>),
+    Suggestion(Box<dyn fmt::Display + Send + Sync + 'static>),
+}
+
+impl HelpInfo {
+    /// The label printed before this entry
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            HelpInfo::Note(_) => "Note",
+            HelpInfo::Warning(_) => "Warning",
+            HelpInfo::Suggestion(_) => "Suggestion",
+        }
+    }
+
+    pub(crate) fn body(&self) -> &(dyn fmt::Display + Send + Sync + 'static) {
+        match self {
+            HelpInfo::Note(body) | HelpInfo::Warning(body) | HelpInfo::Suggestion(body) => &**body,
+        }
+    }
+
+    /// Writes this entry with its label styled with the given theme and its
+    /// body redacted
+    pub(crate) fn fmt_styled<W: fmt::Write>(
+        &self,
+        theme: &Theme,
+        redactions: &[Redaction],
+        f: &mut W,
+    ) -> fmt::Result {
+        let element = match self {
+            HelpInfo::Note(_) => Element::Note,
+            HelpInfo::Warning(_) => Element::Warning,
+            HelpInfo::Suggestion(_) => Element::Suggestion,
+        };
+
+        let body = Redacted {
+            redactions,
+            value: self.body(),
+        };
+
+        write!(f, "{}: {}", theme.paint(element, self.label()), body)
+    }
+}
+
+impl fmt::Debug for HelpInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple(self.label())
+            .field(&self.body().to_string())
+            .finish()
+    }
+}
+
+/// A block of text attached to a report under its own header
+pub(crate) struct CustomSection {
+    pub(crate) header: Box<dyn fmt::Display + Send + Sync + 'static>,
+    pub(crate) body: Box<dyn fmt::Display + Send + Sync + 'static>,
+    /// Only print this section when the report is formatted with `{:#?}`
+    pub(crate) alternate_only: bool,
+}
+
+impl fmt::Display for CustomSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use core::fmt::Write as _;
+
+        write!(f, "{}:", self.header)?;
+        writeln!(f)?;
+        write!(indented(f), "{}", self.body)
+    }
+}
+
+impl fmt::Debug for CustomSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomSection")
+            .field("header", &self.header.to_string())
+            .field("body", &self.body.to_string())
+            .field("alternate_only", &self.alternate_only)
+            .finish()
+    }
+}
+
+/// Extension trait for attaching notes, warnings, suggestions and custom
+/// sections to error reports, assuming stable-eyre's hook is installed.
+///
+/// Custom sections are printed after the `Caused by:` section of the report,
+/// followed by notes, warnings and suggestions, each in the order they were
+/// added.
+///
+/// # Example
+///
+/// ```rust
+/// use stable_eyre::{eyre::eyre, Section};
+/// stable_eyre::install().unwrap();
+///
+/// let report = eyre!("config file is missing the `name` key")
+///     .suggestion("add `name = \"...\"` to the top of the config file");
+///
+/// assert!(format!("{:?}", report).contains("Suggestion: add `name"));
+/// ```
+pub trait Section: private::Sealed {
+    /// The return type of each method after adding an entry
+    type Return;
+
+    /// Add a note to an error report, providing additional context
+    fn note<D>(self, note: D) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static;
+
+    /// Add a note to an error report, lazily evaluated only on `Err`
+    fn with_note<D, F>(self, note: F) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> D;
+
+    /// Add a warning to an error report, describing something that may have
+    /// contributed to the error
+    fn warning<D>(self, warning: D) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static;
+
+    /// Add a warning to an error report, lazily evaluated only on `Err`
+    fn with_warning<D, F>(self, warning: F) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> D;
+
+    /// Add a suggestion to an error report, describing how to fix the error
+    fn suggestion<D>(self, suggestion: D) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static;
+
+    /// Add a suggestion to an error report, lazily evaluated only on `Err`
+    fn with_suggestion<D, F>(self, suggestion: F) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> D;
+
+    /// Add a section to an error report, printing the header followed by a
+    /// colon and the body indented on the lines below it
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use stable_eyre::{eyre::eyre, Section};
+    /// stable_eyre::install().unwrap();
+    ///
+    /// let stderr = "error: unrecognized option `--frobnicate`\nusage: child [OPTIONS]";
+    /// let report = eyre!("child process exited with status 2").section("Stderr", stderr);
+    ///
+    /// assert!(format!("{:?}", report).contains("Stderr:\n    error: unrecognized option"));
+    /// ```
+    fn section<H, B>(self, header: H, body: B) -> Self::Return
+    where
+        H: fmt::Display + Send + Sync + 'static,
+        B: fmt::Display + Send + Sync + 'static;
+
+    /// Add a section to an error report, with the body lazily evaluated only
+    /// on `Err`
+    fn with_section<H, B, F>(self, header: H, body: F) -> Self::Return
+    where
+        H: fmt::Display + Send + Sync + 'static,
+        B: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> B;
+
+    /// Add a section to an error report that is only printed when the report
+    /// is formatted with `{:#?}`
+    ///
+    /// With `{:#?}` the `Debug` output of the error is followed by every
+    /// section and then the notes, warnings and suggestions of the report.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use stable_eyre::{eyre::eyre, Section};
+    /// stable_eyre::install().unwrap();
+    ///
+    /// let report = eyre!("request failed")
+    ///     .alternate_section("Response", "HTTP/1.1 503 Service Unavailable")
+    ///     .note("the service may be restarting");
+    ///
+    /// let alternate = format!("{:#?}", report);
+    /// assert!(alternate.contains("Response:\n    HTTP/1.1 503"));
+    /// assert!(alternate.contains("Note: the service may be restarting"));
+    /// assert!(!format!("{:?}", report).contains("Response:"));
+    /// ```
+    fn alternate_section<H, B>(self, header: H, body: B) -> Self::Return
+    where
+        H: fmt::Display + Send + Sync + 'static,
+        B: fmt::Display + Send + Sync + 'static;
+}
+
+fn push_help(mut report: Report, help: HelpInfo) -> Report {
+    if let Some(handler) = report.handler_mut().downcast_mut::<Handler>() {
+        handler.help.push(help);
+    }
+
+    report
+}
+
+fn push_section<H, B>(mut report: Report, header: H, body: B, alternate_only: bool) -> Report
+where
+    H: fmt::Display + Send + Sync + 'static,
+    B: fmt::Display + Send + Sync + 'static,
+{
+    if let Some(handler) = report.handler_mut().downcast_mut::<Handler>() {
+        handler.sections.push(CustomSection {
+            header: Box::new(header),
+            body: Box::new(body),
+            alternate_only,
+        });
+    }
+
+    report
+}
+
+impl Section for Report {
+    type Return = Report;
+
+    fn note<D>(self, note: D) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+    {
+        push_help(self, HelpInfo::Note(Box::new(note)))
+    }
+
+    fn with_note<D, F>(self, note: F) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> D,
+    {
+        self.note(note())
+    }
+
+    fn warning<D>(self, warning: D) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+    {
+        push_help(self, HelpInfo::Warning(Box::new(warning)))
+    }
+
+    fn with_warning<D, F>(self, warning: F) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> D,
+    {
+        self.warning(warning())
+    }
+
+    fn suggestion<D>(self, suggestion: D) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+    {
+        push_help(self, HelpInfo::Suggestion(Box::new(suggestion)))
+    }
+
+    fn with_suggestion<D, F>(self, suggestion: F) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> D,
+    {
+        self.suggestion(suggestion())
+    }
+
+    fn section<H, B>(self, header: H, body: B) -> Self::Return
+    where
+        H: fmt::Display + Send + Sync + 'static,
+        B: fmt::Display + Send + Sync + 'static,
+    {
+        push_section(self, header, body, false)
+    }
+
+    fn with_section<H, B, F>(self, header: H, body: F) -> Self::Return
+    where
+        H: fmt::Display + Send + Sync + 'static,
+        B: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> B,
+    {
+        self.section(header, body())
+    }
+
+    fn alternate_section<H, B>(self, header: H, body: B) -> Self::Return
+    where
+        H: fmt::Display + Send + Sync + 'static,
+        B: fmt::Display + Send + Sync + 'static,
+    {
+        push_section(self, header, body, true)
+    }
+}
+
+impl<T, E> Section for Result<T, E>
+where
+    E: Into<Report>,
+{
+    type Return = Result<T, Report>;
+
+    #[track_caller]
+    fn note<D>(self, note: D) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error) => Err(error.into().note(note)),
+        }
+    }
+
+    #[track_caller]
+    fn with_note<D, F>(self, note: F) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> D,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error) => Err(error.into().with_note(note)),
+        }
+    }
+
+    #[track_caller]
+    fn warning<D>(self, warning: D) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error) => Err(error.into().warning(warning)),
+        }
+    }
+
+    #[track_caller]
+    fn with_warning<D, F>(self, warning: F) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> D,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error) => Err(error.into().with_warning(warning)),
+        }
+    }
+
+    #[track_caller]
+    fn suggestion<D>(self, suggestion: D) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error) => Err(error.into().suggestion(suggestion)),
+        }
+    }
+
+    #[track_caller]
+    fn with_suggestion<D, F>(self, suggestion: F) -> Self::Return
+    where
+        D: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> D,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error) => Err(error.into().with_suggestion(suggestion)),
+        }
+    }
+
+    #[track_caller]
+    fn section<H, B>(self, header: H, body: B) -> Self::Return
+    where
+        H: fmt::Display + Send + Sync + 'static,
+        B: fmt::Display + Send + Sync + 'static,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error) => Err(error.into().section(header, body)),
+        }
+    }
+
+    #[track_caller]
+    fn with_section<H, B, F>(self, header: H, body: F) -> Self::Return
+    where
+        H: fmt::Display + Send + Sync + 'static,
+        B: fmt::Display + Send + Sync + 'static,
+        F: FnOnce() -> B,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error) => Err(error.into().with_section(header, body)),
+        }
+    }
+
+    #[track_caller]
+    fn alternate_section<H, B>(self, header: H, body: B) -> Self::Return
+    where
+        H: fmt::Display + Send + Sync + 'static,
+        B: fmt::Display + Send + Sync + 'static,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(error) => Err(error.into().alternate_section(header, body)),
+        }
+    }
+}
+
+pub(crate) mod private {
+    use eyre::Report;
+
+    pub trait Sealed {}
+
+    impl Sealed for Report {}
+    impl<T, E> Sealed for Result<T, E> where E: Into<Report> {}
+}
@@ -0,0 +1,108 @@
+use crate::{
+    redact::{Redacted, Redaction},
+    theme::{Element, Theme},
+    Handler,
+};
+use indenter::indented;
+use std::fmt;
+use tracing_error::{SpanTrace, SpanTraceStatus};
+
+/// Extension trait to extract a `SpanTrace` from an `eyre::Report`, assuming
+/// stable-eyre's hook is installed.
+///
+/// Span traces are only captured when a `tracing_error::ErrorLayer` is part
+/// of the current subscriber.
+pub trait SpanTraceExt {
+    /// Returns a reference to the captured span trace if one exists
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use stable_eyre::{eyre::eyre, SpanTraceExt};
+    /// use tracing_error::{ErrorLayer, SpanTraceStatus};
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// stable_eyre::install().unwrap();
+    /// tracing_subscriber::registry().with(ErrorLayer::default()).init();
+    ///
+    /// let report = tracing::info_span!("load_config", path = "app.toml")
+    ///     .in_scope(|| eyre!("capture a report"));
+    ///
+    /// let span_trace = report.span_trace().unwrap();
+    /// assert_eq!(span_trace.status(), SpanTraceStatus::CAPTURED);
+    /// ```
+    fn span_trace(&self) -> Option<&SpanTrace>;
+}
+
+impl SpanTraceExt for eyre::Report {
+    fn span_trace(&self) -> Option<&SpanTrace> {
+        self.handler()
+            .downcast_ref::<Handler>()
+            .and_then(|handler| handler.span_trace.as_ref())
+    }
+}
+
+/// Captures a span trace if the current subscriber supports it
+pub(crate) fn capture() -> Option<SpanTrace> {
+    let span_trace = SpanTrace::capture();
+
+    if span_trace.status() == SpanTraceStatus::CAPTURED {
+        Some(span_trace)
+    } else {
+        None
+    }
+}
+
+/// Writes a numbered list of the spans in a span trace, with their redacted
+/// fields and source locations
+pub(crate) fn fmt_span_trace<W: fmt::Write>(
+    span_trace: &SpanTrace,
+    theme: &Theme,
+    redactions: &[Redaction],
+    f: &mut W,
+) -> fmt::Result {
+    let mut result = Ok(());
+    let mut n = 0;
+
+    span_trace.with_spans(|metadata, fields| {
+        let name = format!("{}::{}", metadata.target(), metadata.name());
+        let location = metadata.file().map(|file| match metadata.line() {
+            Some(line) => format!("{}:{}", file, line),
+            None => file.to_string(),
+        });
+
+        result = writeln!(f).and_then(|_| {
+            let mut f = indented(f).ind(n);
+            let fields = Redacted {
+                redactions,
+                value: fields,
+            };
+            fmt_span(&name, fields, location.as_deref(), theme, &mut f)
+        });
+        n += 1;
+
+        result.is_ok()
+    });
+
+    result
+}
+
+fn fmt_span<W: fmt::Write>(
+    name: &str,
+    fields: Redacted<'_, &str>,
+    location: Option<&str>,
+    theme: &Theme,
+    f: &mut W,
+) -> fmt::Result {
+    write!(f, "{}", theme.paint(Element::Function, name))?;
+
+    if !fields.value.is_empty() {
+        write!(f, " with {}", fields)?;
+    }
+
+    if let Some(location) = location {
+        write!(f, "\n    at {}", theme.paint(Element::File, location))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,424 @@
+//! Resolving the raw backtraces printed by `HookBuilder::offline_symbolization`
+//!
+//! This module backs the `stable-eyre-symbolize` binary, which reads a report
+//! containing raw backtrace blocks from stdin and prints it with every block
+//! replaced by a resolved backtrace:
+//!
+//! ```text
+//! stable-eyre-symbolize target/release/app.debug < report.txt
+//! ```
+//!
+//! Each module of a block is matched with the debug file of the same GNU
+//! build-id, or failing that the debug file of the same file name. Frames of
+//! modules without a matching debug file are printed as `<unknown>`.
+use crate::{
+    eyre::{eyre, Result, WrapErr},
+    frame::FrameFormatter,
+    modules::{BuildId, BLOCK_BEGIN, BLOCK_END},
+    paths::PathRewrites,
+    theme::Theme,
+    Frame, Verbosity,
+};
+use addr2line::ObjectContext;
+use object::Object;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Replaces every raw backtrace block in `input` with the frames resolved
+/// from the given unstripped executables and shared objects
+///
+/// Everything outside of the blocks is passed through unchanged, apart from
+/// `Stack backtrace (unsymbolized):` headers which become `Stack backtrace:`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// let report = std::fs::read_to_string("report.txt")?;
+/// let resolved = stable_eyre::symbolize::symbolize(&report, &["target/release/app"])?;
+///
+/// println!("{}", resolved);
+/// # Ok::<_, stable_eyre::eyre::Report>(())
+/// ```
+pub fn symbolize<P: AsRef<Path>>(input: &str, debug_files: &[P]) -> Result<String> {
+    let debug_files = debug_files
+        .iter()
+        .map(|path| DebugFile::load(path.as_ref()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut output = String::new();
+    let mut lines = input.lines();
+
+    while let Some(line) = lines.next() {
+        if line.trim() == BLOCK_BEGIN {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            let mut block = Vec::new();
+
+            loop {
+                match lines.next().map(str::trim) {
+                    Some(BLOCK_END) => break,
+                    Some(line) => block.push(line),
+                    None => return Err(eyre!("backtrace block without `{}` line", BLOCK_END)),
+                }
+            }
+
+            let frames = RawBacktrace::parse(&block)?.resolve(&debug_files);
+
+            let formatter = FrameFormatter {
+                theme: &Theme::default(),
+                verbosity: Verbosity::Full,
+                source_snippets: 0,
+                paths: &PathRewrites::default(),
+                omitted: None,
+            };
+            let mut resolved = String::new();
+            formatter.fmt_frames(&frames, &mut resolved)?;
+
+            for (i, line) in resolved.trim_start_matches('\n').lines().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
+                output.push_str(indent);
+                output.push_str(line);
+            }
+        } else {
+            output.push_str(&line.replace("Stack backtrace (unsymbolized):", "Stack backtrace:"));
+        }
+
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+/// The contents of a raw backtrace block
+struct RawBacktrace {
+    modules: Vec<RawModule>,
+    frames: Vec<RawFrame>,
+}
+
+struct RawModule {
+    base: usize,
+    build_id: Option<String>,
+    path: PathBuf,
+}
+
+struct RawFrame {
+    ip: usize,
+    module: Option<usize>,
+}
+
+impl RawBacktrace {
+    /// Parses the lines between the first and last line of a block
+    fn parse(lines: &[&str]) -> Result<Self> {
+        let mut backtrace = RawBacktrace {
+            modules: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        for line in lines {
+            let parsed = match line.split_once(' ') {
+                Some(("module", fields)) => {
+                    RawModule::parse(fields).map(|module| backtrace.modules.push(module))
+                }
+                Some(("frame", fields)) => {
+                    RawFrame::parse(fields).map(|frame| backtrace.frames.push(frame))
+                }
+                _ => None,
+            };
+
+            if parsed.is_none() {
+                return Err(eyre!("malformed line in backtrace block: `{}`", line));
+            }
+        }
+
+        Ok(backtrace)
+    }
+
+    fn resolve(&self, debug_files: &[DebugFile]) -> Vec<Frame> {
+        let contexts: Vec<Option<&DebugFile>> = self
+            .modules
+            .iter()
+            .map(|module| module.find_debug_file(debug_files))
+            .collect();
+
+        let mut frames = Vec::new();
+
+        for raw in &self.frames {
+            let module = raw
+                .module
+                .and_then(|i| Some((self.modules.get(i)?, contexts[i]?)));
+            let resolved = match module {
+                Some((module, debug_file)) => {
+                    // Frames point at the return address, the call is the
+                    // instruction before it
+                    let address = raw.ip.wrapping_sub(module.base).wrapping_sub(1);
+                    debug_file.resolve(address as u64)
+                }
+                None => Vec::new(),
+            };
+
+            if resolved.is_empty() {
+                frames.push(Frame {
+                    n: frames.len(),
+                    name: None,
+                    lineno: None,
+                    colno: None,
+                    filename: None,
+                    ip: raw.ip,
+                });
+            }
+
+            for symbol in resolved {
+                frames.push(Frame {
+                    n: frames.len(),
+                    name: symbol.name,
+                    lineno: symbol.lineno,
+                    colno: symbol.colno,
+                    filename: symbol.filename,
+                    ip: raw.ip,
+                });
+            }
+        }
+
+        frames
+    }
+}
+
+impl RawModule {
+    /// Parses `<index> base=0x... build-id=<hex|-> path=<path>`
+    fn parse(fields: &str) -> Option<Self> {
+        let (_index, fields) = fields.split_once(' ')?;
+        let (base, fields) = fields.strip_prefix("base=")?.split_once(' ')?;
+        let (build_id, fields) = fields.strip_prefix("build-id=")?.split_once(' ')?;
+        let path = fields.strip_prefix("path=")?;
+
+        Some(RawModule {
+            base: parse_hex(base)?,
+            build_id: Some(build_id).filter(|id| *id != "-").map(str::to_owned),
+            path: PathBuf::from(path),
+        })
+    }
+
+    fn find_debug_file<'a>(&self, debug_files: &'a [DebugFile]) -> Option<&'a DebugFile> {
+        let by_build_id = self.build_id.as_ref().and_then(|build_id| {
+            debug_files
+                .iter()
+                .find(|file| file.build_id.as_ref() == Some(build_id))
+        });
+
+        by_build_id.or_else(|| {
+            let name = self.path.file_name()?;
+            debug_files
+                .iter()
+                .find(|file| file.path.file_name() == Some(name))
+        })
+    }
+}
+
+impl RawFrame {
+    /// Parses `<index> ip=0x... module=<index|->`
+    fn parse(fields: &str) -> Option<Self> {
+        let (_index, fields) = fields.split_once(' ')?;
+        let (ip, module) = fields.strip_prefix("ip=")?.split_once(' ')?;
+        let module = match module.strip_prefix("module=")? {
+            "-" => None,
+            index => Some(index.parse().ok()?),
+        };
+
+        Some(RawFrame {
+            ip: parse_hex(ip)?,
+            module,
+        })
+    }
+}
+
+fn parse_hex(value: &str) -> Option<usize> {
+    usize::from_str_radix(value.strip_prefix("0x")?, 16).ok()
+}
+
+/// An unstripped executable or shared object loaded for resolving addresses
+struct DebugFile {
+    path: PathBuf,
+    build_id: Option<String>,
+    context: ObjectContext,
+    /// The symbol table sorted by address, used when there is no DWARF for
+    /// an address
+    symbols: Vec<(u64, String)>,
+}
+
+/// A symbol resolved from a debug file
+struct Symbol {
+    name: Option<String>,
+    lineno: Option<u32>,
+    colno: Option<u32>,
+    filename: Option<PathBuf>,
+}
+
+impl DebugFile {
+    fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path).wrap_err_with(|| format!("failed to read {}", path.display()))?;
+        let file = object::File::parse(&*data)
+            .wrap_err_with(|| format!("failed to parse {}", path.display()))?;
+
+        let build_id = match file.build_id() {
+            Ok(Some(build_id)) => Some(BuildId(build_id).to_string()),
+            _ => None,
+        };
+        let context = ObjectContext::new(&file)
+            .wrap_err_with(|| format!("failed to load the DWARF of {}", path.display()))?;
+
+        let mut symbols: Vec<(u64, String)> = file
+            .symbol_map()
+            .symbols()
+            .iter()
+            .map(|symbol| (symbol.address(), symbol.name().to_owned()))
+            .collect();
+        symbols.sort_by_key(|&(address, _)| address);
+
+        Ok(DebugFile {
+            path: path.to_owned(),
+            build_id,
+            context,
+            symbols,
+        })
+    }
+
+    /// Resolves an address relative to the file's load address, innermost
+    /// inlined function first
+    fn resolve(&self, address: u64) -> Vec<Symbol> {
+        let mut resolved = Vec::new();
+
+        if let Ok(mut frames) = self.context.find_frames(address).skip_all_loads() {
+            while let Ok(Some(frame)) = frames.next() {
+                let name = frame
+                    .function
+                    .as_ref()
+                    .and_then(|function| function.raw_name().ok())
+                    .map(|name| demangle(&name));
+                let location = frame.location.as_ref();
+
+                resolved.push(Symbol {
+                    name,
+                    lineno: location.and_then(|location| location.line),
+                    colno: location.and_then(|location| location.column),
+                    filename: location
+                        .and_then(|location| location.file)
+                        .map(PathBuf::from),
+                });
+            }
+        }
+
+        let symbol = self.symbol_name(address);
+
+        match resolved.last_mut() {
+            Some(outermost) if outermost.name.is_none() => outermost.name = symbol,
+            Some(_) => {}
+            None if symbol.is_some() => resolved.push(Symbol {
+                name: symbol,
+                lineno: None,
+                colno: None,
+                filename: None,
+            }),
+            None => {}
+        }
+
+        resolved
+    }
+
+    /// Finds the name of the symbol table entry containing `address`
+    fn symbol_name(&self, address: u64) -> Option<String> {
+        let i = self
+            .symbols
+            .partition_point(|&(start, _)| start <= address)
+            .checked_sub(1)?;
+
+        Some(demangle(&self.symbols[i].1))
+    }
+}
+
+fn demangle(name: &str) -> String {
+    format!("{:#}", rustc_demangle::demangle(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::{self, Module};
+
+    #[test]
+    fn raw_backtrace_round_trip() {
+        let modules = [
+            Module {
+                path: PathBuf::from("/usr/bin/app"),
+                base: 0x5000,
+                build_id: Some(vec![0xab, 0xcd, 0x01]),
+                segments: vec![(0x5000, 0x6000)],
+            },
+            Module {
+                path: PathBuf::from("/usr/lib/unused.so"),
+                base: 0x6000,
+                build_id: None,
+                segments: vec![(0x6000, 0x7000)],
+            },
+            Module {
+                path: PathBuf::from("/usr/lib/libc.so.6"),
+                base: 0x7000,
+                build_id: None,
+                segments: vec![(0x7000, 0x8000)],
+            },
+        ];
+        let ips = [0x5010, 0x7020, 0x9999, 0x5fff];
+
+        let mut block = String::new();
+        modules::fmt_raw_backtrace(&ips, &modules, &mut block).unwrap();
+        let lines: Vec<&str> = block.lines().collect();
+        assert_eq!(lines.first(), Some(&BLOCK_BEGIN));
+        assert_eq!(lines.last(), Some(&BLOCK_END));
+
+        let parsed = RawBacktrace::parse(&lines[1..lines.len() - 1]).unwrap();
+
+        let frames: Vec<_> = parsed.frames.iter().map(|f| (f.ip, f.module)).collect();
+        assert_eq!(
+            frames,
+            [
+                (0x5010, Some(0)),
+                (0x7020, Some(1)),
+                (0x9999, None),
+                (0x5fff, Some(0))
+            ]
+        );
+
+        let modules: Vec<_> = parsed
+            .modules
+            .iter()
+            .map(|m| (m.base, m.build_id.as_deref(), m.path.to_str().unwrap()))
+            .collect();
+        assert_eq!(
+            modules,
+            [
+                (0x5000, Some("abcd01"), "/usr/bin/app"),
+                (0x7000, None, "/usr/lib/libc.so.6")
+            ]
+        );
+    }
+
+    #[test]
+    fn resolved_frames_keep_indentation() {
+        let input = format!(
+            "Stack backtrace (unsymbolized):\n    {}\n    frame 0 ip=0x0000000000001234 module=-\n    {}\n",
+            BLOCK_BEGIN, BLOCK_END
+        );
+        let output = symbolize::<&str>(&input, &[]).unwrap();
+
+        assert!(output.starts_with("Stack backtrace:\n       0: 0x0000000000001234 - <unknown>"));
+    }
+
+    #[test]
+    fn unterminated_block() {
+        let input = format!("{}\nframe 0 ip=0x0000000000001234 module=-\n", BLOCK_BEGIN);
+
+        assert!(symbolize::<&str>(&input, &[]).is_err());
+    }
+}
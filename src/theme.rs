@@ -0,0 +1,159 @@
+use std::fmt;
+
+#[cfg(feature = "color")]
+use owo_colors::Style;
+
+/// A set of styles used to color the output of error reports
+///
+/// The default theme applies no styles at all.
+#[derive(Debug, Clone, Default)]
+pub struct Theme {
+    #[cfg(feature = "color")]
+    message: Style,
+    #[cfg(feature = "color")]
+    cause_index: Style,
+    #[cfg(feature = "color")]
+    function: Style,
+    #[cfg(feature = "color")]
+    file: Style,
+    #[cfg(feature = "color")]
+    dependency: Style,
+    #[cfg(feature = "color")]
+    current_line: Style,
+    #[cfg(feature = "color")]
+    note: Style,
+    #[cfg(feature = "color")]
+    warning: Style,
+    #[cfg(feature = "color")]
+    suggestion: Style,
+}
+
+#[cfg(feature = "color")]
+impl Theme {
+    /// Creates a theme without any styles
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The theme used by `HookBuilder::default()`, suited for dark terminal
+    /// backgrounds
+    pub fn dark() -> Self {
+        Self {
+            message: Style::new().bright_red(),
+            cause_index: Style::new().bright_red(),
+            function: Style::new().bright_green(),
+            file: Style::new().purple(),
+            dependency: Style::new().cyan(),
+            current_line: Style::new().white().bold(),
+            note: Style::new().bright_cyan(),
+            warning: Style::new().bright_yellow(),
+            suggestion: Style::new().bright_cyan(),
+        }
+    }
+
+    /// Styles the error message of a report
+    pub fn message(mut self, style: Style) -> Self {
+        self.message = style;
+        self
+    }
+
+    /// Styles the indices of the `Caused by:` section
+    pub fn cause_index(mut self, style: Style) -> Self {
+        self.cause_index = style;
+        self
+    }
+
+    /// Styles the function names of backtrace frames
+    pub fn function(mut self, style: Style) -> Self {
+        self.function = style;
+        self
+    }
+
+    /// Styles the file paths of backtrace frames
+    pub fn file(mut self, style: Style) -> Self {
+        self.file = style;
+        self
+    }
+
+    /// Styles the function names of backtrace frames from dependencies or the
+    /// rust toolchain, in place of the `function` style
+    pub fn dependency(mut self, style: Style) -> Self {
+        self.dependency = style;
+        self
+    }
+
+    /// Styles the line of a source snippet that a backtrace frame is at
+    pub fn current_line(mut self, style: Style) -> Self {
+        self.current_line = style;
+        self
+    }
+
+    /// Styles the `Note:` label of notes attached to a report
+    pub fn note(mut self, style: Style) -> Self {
+        self.note = style;
+        self
+    }
+
+    /// Styles the `Warning:` label of warnings attached to a report
+    pub fn warning(mut self, style: Style) -> Self {
+        self.warning = style;
+        self
+    }
+
+    /// Styles the `Suggestion:` label of suggestions attached to a report
+    pub fn suggestion(mut self, style: Style) -> Self {
+        self.suggestion = style;
+        self
+    }
+}
+
+/// The part of a report being styled by a `Theme`
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Element {
+    Message,
+    CauseIndex,
+    Function,
+    File,
+    Dependency,
+    CurrentLine,
+    Note,
+    Warning,
+    Suggestion,
+}
+
+impl Theme {
+    /// Applies the style of the given element to `value`
+    #[cfg(feature = "color")]
+    pub(crate) fn paint<T: fmt::Display>(&self, element: Element, value: T) -> impl fmt::Display {
+        let style = match element {
+            Element::Message => self.message,
+            Element::CauseIndex => self.cause_index,
+            Element::Function => self.function,
+            Element::File => self.file,
+            Element::Dependency => self.dependency,
+            Element::CurrentLine => self.current_line,
+            Element::Note => self.note,
+            Element::Warning => self.warning,
+            Element::Suggestion => self.suggestion,
+        };
+
+        style.style(value)
+    }
+
+    /// Applies the style of the given element to `value`
+    #[cfg(not(feature = "color"))]
+    pub(crate) fn paint<T: fmt::Display>(&self, element: Element, value: T) -> impl fmt::Display {
+        let _ = element;
+        value
+    }
+}
+
+/// Is colored output appropriate for stderr?
+///
+/// Colors are disabled when stderr isn't a terminal or `NO_COLOR` is set.
+#[cfg(feature = "color")]
+pub(crate) fn color_enabled() -> bool {
+    use std::io::IsTerminal;
+
+    std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal()
+}
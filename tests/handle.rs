@@ -0,0 +1,24 @@
+use stable_eyre::{eyre::eyre, Handler, HookBuilder, Verbosity};
+
+fn verbosity() -> Verbosity {
+    eyre!("a report")
+        .handler()
+        .downcast_ref::<Handler>()
+        .unwrap()
+        .verbosity()
+}
+
+#[test]
+fn clearing_the_verbosity_restores_the_env_verbosity() {
+    std::env::set_var("STABLE_EYRE_VERBOSITY", "full");
+    std::env::set_var("RUST_BACKTRACE", "0");
+
+    let handle = HookBuilder::from_env().install().unwrap();
+    assert_eq!(verbosity(), Verbosity::Full);
+
+    handle.set_verbosity(Some(Verbosity::Medium));
+    assert_eq!(verbosity(), Verbosity::Medium);
+
+    handle.set_verbosity(None);
+    assert_eq!(verbosity(), Verbosity::Full);
+}
@@ -0,0 +1,41 @@
+use stable_eyre::{eyre::eyre, HookBuilder, Report};
+
+#[inline(never)]
+fn recurse(depth: usize) -> Report {
+    if depth == 0 {
+        eyre!("too deep")
+    } else {
+        std::hint::black_box(recurse(depth - 1))
+    }
+}
+
+fn omitted_line(report: &Report) -> String {
+    format!("{:?}", report)
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("..."))
+        .expect("frames are omitted")
+        .to_owned()
+}
+
+#[test]
+fn bounds_the_walk() {
+    HookBuilder::default()
+        .capture_backtrace_by_default(true)
+        .max_frames(20)
+        .install()
+        .unwrap();
+
+    let shallow = omitted_line(&recurse(200));
+    assert!(shallow.ends_with(" frames omitted ..."), "{}", shallow);
+    assert!(!shallow.contains("at least"), "{}", shallow);
+
+    // Deeper than the walk goes
+    let deep = std::thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(|| omitted_line(&recurse(10_000)))
+        .unwrap()
+        .join()
+        .unwrap();
+    assert!(deep.starts_with("... at least "), "{}", deep);
+}
@@ -0,0 +1,44 @@
+use stable_eyre::{eyre::eyre, HookBuilder, Report};
+
+#[inline(never)]
+fn recurse(depth: usize) -> Report {
+    if depth == 0 {
+        eyre!("too deep")
+    } else {
+        std::hint::black_box(recurse(depth - 1))
+    }
+}
+
+#[test]
+fn folds_recursion() {
+    HookBuilder::default()
+        .capture_backtrace_by_default(true)
+        .install()
+        .unwrap();
+
+    let report = recurse(50);
+    let text = format!("{:?}", report);
+    let repeat = text
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("[frames "))
+        .expect("the recursion is folded");
+
+    assert!(
+        repeat.ends_with(" repeat the previous frame 49 times]"),
+        "{}",
+        repeat
+    );
+
+    #[cfg(feature = "serde")]
+    {
+        use stable_eyre::ReportExt;
+
+        let json = report.to_json();
+        let repeated = json["repeated_frames"].as_array().unwrap();
+
+        assert_eq!(repeated.len(), 1);
+        assert_eq!(repeated[0]["period"], 1);
+        assert_eq!(repeated[0]["times"], 49);
+    }
+}
@@ -0,0 +1,18 @@
+use stable_eyre::{eyre::eyre, HookBuilder, Redaction};
+
+#[test]
+fn redacts_display() {
+    HookBuilder::default()
+        .redact(Redaction::with(|text| text.replace("s3cret", "[REDACTED]")))
+        .install()
+        .unwrap();
+
+    let report = eyre!("connect postgres://app:s3cret@db/app failed")
+        .wrap_err("Authorization: Bearer s3cret");
+
+    assert_eq!(report.to_string(), "Authorization: Bearer [REDACTED]");
+    assert_eq!(
+        format!("{:#}", report),
+        "Authorization: Bearer [REDACTED]: connect postgres://app:[REDACTED]@db/app failed"
+    );
+}